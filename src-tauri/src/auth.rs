@@ -1,9 +1,14 @@
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rand::RngCore;
 use rusqlite::{params, Connection, Result as SqlResult};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::State;
 
+type DbPool = Pool<SqliteConnectionManager>;
+
 // ============================================================================
 // Types
 // ============================================================================
@@ -12,8 +17,9 @@ use tauri::State;
 pub struct User {
     pub id: i64,
     pub username: String,
-    pub email: String,
+    pub email: Option<String>,
     pub created_at: String,
+    pub account_status: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,6 +33,8 @@ pub struct RegisterRequest {
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,97 +42,107 @@ pub struct AuthResponse {
     pub success: bool,
     pub message: String,
     pub user: Option<User>,
+    pub session_token: Option<String>,
 }
 
+// Session token lifetime: a short-lived default session, or a long-lived one
+// when the user opts in to "remember me".
+const SESSION_TTL_DEFAULT: &str = "+8 hours";
+const SESSION_TTL_REMEMBER_ME: &str = "+30 days";
+
 // ============================================================================
 // Database State
 // ============================================================================
 
 pub struct DbState {
-    pub conn: Mutex<Connection>,
+    pub pool: DbPool,
     pub current_user: Mutex<Option<User>>,
 }
 
 impl DbState {
-    pub fn new(db_path: PathBuf) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
-
-        // Create tables
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS users (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                username TEXT NOT NULL UNIQUE,
-                email TEXT NOT NULL UNIQUE,
-                password_hash TEXT NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now'))
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS user_recipes (
-                id TEXT PRIMARY KEY,
-                user_id INTEGER NOT NULL,
-                name TEXT NOT NULL,
-                description TEXT,
-                category TEXT NOT NULL,
-                components TEXT NOT NULL,
-                total_volume REAL NOT NULL,
-                volume_unit TEXT NOT NULL,
-                ph REAL,
-                instructions TEXT,
-                notes TEXT,
-                tags TEXT,
-                created_at TEXT NOT NULL,
-                modified_at TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS user_measurements (
-                id TEXT PRIMARY KEY,
-                user_id INTEGER NOT NULL,
-                protein_name TEXT NOT NULL,
-                date TEXT NOT NULL,
-                absorbance_280 REAL NOT NULL,
-                extinction_coefficient REAL NOT NULL,
-                molecular_weight REAL NOT NULL,
-                path_length REAL NOT NULL,
-                concentration REAL NOT NULL,
-                concentration_molar REAL NOT NULL,
-                notes TEXT,
-                sequence TEXT,
-                batch_number TEXT,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS user_preferences (
-                user_id INTEGER PRIMARY KEY,
-                default_volume REAL NOT NULL DEFAULT 100,
-                default_volume_unit TEXT NOT NULL DEFAULT 'mL',
-                default_concentration_unit TEXT NOT NULL DEFAULT 'M',
-                recent_chemicals TEXT,
-                favorite_recipes TEXT,
-                theme TEXT NOT NULL DEFAULT 'auto',
-                scientific_notation INTEGER NOT NULL DEFAULT 0,
-                decimal_places INTEGER NOT NULL DEFAULT 4,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+    pub fn new(db_path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let manager = SqliteConnectionManager::file(db_path).with_init(|conn| {
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "foreign_keys", true)?;
+            Ok(())
+        });
+        let pool = Pool::new(manager)?;
+
+        let conn = pool.get()?;
+        crate::migrations::run_migrations(&conn)?;
 
         Ok(Self {
-            conn: Mutex::new(conn),
+            pool,
             current_user: Mutex::new(None),
         })
     }
 }
 
+// ============================================================================
+// Sessions
+// ============================================================================
+
+/// Generates a cryptographically random, hex-encoded session token.
+fn generate_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Creates a session row for `user_id` and returns its token.
+///
+/// `remember_me` selects between a short default TTL and a long-lived
+/// "remember me" TTL.
+fn create_session(conn: &Connection, user_id: i64, remember_me: bool) -> SqlResult<String> {
+    let token = generate_session_token();
+    let ttl = if remember_me {
+        SESSION_TTL_REMEMBER_ME
+    } else {
+        SESSION_TTL_DEFAULT
+    };
+
+    conn.execute(
+        "INSERT INTO sessions (token, user_id, created_at, expires_at, last_seen)
+         VALUES (?, ?, datetime('now'), datetime('now', ?), datetime('now'))",
+        params![&token, user_id, ttl],
+    )?;
+
+    Ok(token)
+}
+
+/// Deletes any session rows whose `expires_at` has already passed.
+fn prune_expired_sessions(conn: &Connection) -> SqlResult<()> {
+    conn.execute(
+        "DELETE FROM sessions WHERE expires_at <= datetime('now')",
+        [],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Password hashing
+// ============================================================================
+//
+// The hash algorithm and cost are stored alongside each user so the target
+// cost can be raised later without invalidating existing accounts: a login
+// that verifies against a stale `pw_algo`/`pw_cost` transparently rehashes
+// the just-verified plaintext at the current target and updates the row.
+
+const PW_ALGO_BCRYPT: &str = "bcrypt";
+const CURRENT_PW_COST: i32 = bcrypt::DEFAULT_COST as i32;
+
+/// Hashes `password` at the current target algorithm and cost.
+fn hash_password(password: &str) -> Result<(String, &'static str, i32), bcrypt::BcryptError> {
+    let hash = bcrypt::hash(password, CURRENT_PW_COST as u32)?;
+    Ok((hash, PW_ALGO_BCRYPT, CURRENT_PW_COST))
+}
+
+/// Whether a hash created with `pw_algo`/`pw_cost` falls short of the
+/// current target and should be upgraded.
+fn needs_rehash(pw_algo: &str, pw_cost: i32) -> bool {
+    pw_algo != PW_ALGO_BCRYPT || pw_cost < CURRENT_PW_COST
+}
+
 // ============================================================================
 // Tauri Commands
 // ============================================================================
@@ -140,6 +158,7 @@ pub fn register_user(
             success: false,
             message: "Username, email, and password are required".to_string(),
             user: None,
+            session_token: None,
         });
     }
 
@@ -148,14 +167,18 @@ pub fn register_user(
             success: false,
             message: "Password must be at least 6 characters".to_string(),
             user: None,
+            session_token: None,
         });
     }
 
     // Hash password
-    let password_hash = bcrypt::hash(&request.password, bcrypt::DEFAULT_COST)
+    let (password_hash, pw_algo, pw_cost) = hash_password(&request.password)
         .map_err(|e| format!("Failed to hash password: {}", e))?;
 
-    let conn = db_state.conn.lock().unwrap();
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
 
     // Check if username or email already exists
     let exists: Result<i64, _> = conn.query_row(
@@ -169,13 +192,14 @@ pub fn register_user(
             success: false,
             message: "Username or email already exists".to_string(),
             user: None,
+            session_token: None,
         });
     }
 
     // Insert new user
     conn.execute(
-        "INSERT INTO users (username, email, password_hash) VALUES (?, ?, ?)",
-        params![&request.username, &request.email, &password_hash],
+        "INSERT INTO users (username, email, password_hash, pw_algo, pw_cost) VALUES (?, ?, ?, ?, ?)",
+        params![&request.username, &request.email, &password_hash, pw_algo, pw_cost],
     )
     .map_err(|e| format!("Failed to create user: {}", e))?;
 
@@ -188,10 +212,16 @@ pub fn register_user(
     )
     .map_err(|e| format!("Failed to create user preferences: {}", e))?;
 
+    // The first registered user becomes the lab admin. Guest accounts can
+    // exist before any real registration, so this checks for an existing
+    // admin rather than counting `users` rows.
+    crate::roles::bootstrap_admin_if_needed(&conn, user_id)
+        .map_err(|e| format!("Failed to check/assign admin role: {}", e))?;
+
     // Get created user
     let user = conn
         .query_row(
-            "SELECT id, username, email, created_at FROM users WHERE id = ?",
+            "SELECT id, username, email, created_at, account_status FROM users WHERE id = ?",
             params![user_id],
             |row| {
                 Ok(User {
@@ -199,18 +229,22 @@ pub fn register_user(
                     username: row.get(1)?,
                     email: row.get(2)?,
                     created_at: row.get(3)?,
+                    account_status: row.get(4)?,
                 })
             },
         )
         .map_err(|e| format!("Failed to fetch user: {}", e))?;
 
-    // Set current user
+    // Start a session and set current user
+    let session_token = create_session(&conn, user_id, false)
+        .map_err(|e| format!("Failed to create session: {}", e))?;
     *db_state.current_user.lock().unwrap() = Some(user.clone());
 
     Ok(AuthResponse {
         success: true,
         message: "Registration successful".to_string(),
         user: Some(user),
+        session_token: Some(session_token),
     })
 }
 
@@ -225,14 +259,23 @@ pub fn login_user(
             success: false,
             message: "Username and password are required".to_string(),
             user: None,
+            session_token: None,
         });
     }
 
-    let conn = db_state.conn.lock().unwrap();
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
 
     // Get user from database
-    let user_result: Result<(i64, String, String, String, String), _> = conn.query_row(
-        "SELECT id, username, email, password_hash, created_at FROM users WHERE username = ?",
+    #[allow(clippy::type_complexity)]
+    let user_result: Result<
+        (i64, String, Option<String>, Option<String>, String, String, i32, String),
+        _,
+    > = conn.query_row(
+        "SELECT id, username, email, password_hash, created_at, pw_algo, pw_cost, account_status
+         FROM users WHERE username = ?",
         params![&request.username],
         |row| {
             Ok((
@@ -241,12 +284,25 @@ pub fn login_user(
                 row.get(2)?,
                 row.get(3)?,
                 row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
             ))
         },
     );
 
     match user_result {
-        Ok((id, username, email, password_hash, created_at)) => {
+        Ok((id, username, email, password_hash, created_at, pw_algo, pw_cost, account_status)) => {
+            // Guests have no password to verify against.
+            let Some(password_hash) = password_hash else {
+                return Ok(AuthResponse {
+                    success: false,
+                    message: "Invalid username or password".to_string(),
+                    user: None,
+                    session_token: None,
+                });
+            };
+
             // Verify password
             let password_valid = bcrypt::verify(&request.password, &password_hash)
                 .map_err(|e| format!("Password verification error: {}", e))?;
@@ -256,41 +312,288 @@ pub fn login_user(
                     success: false,
                     message: "Invalid username or password".to_string(),
                     user: None,
+                    session_token: None,
                 });
             }
 
+            // Transparently upgrade the hash if it's below the current target.
+            if needs_rehash(&pw_algo, pw_cost) {
+                if let Ok((new_hash, new_algo, new_cost)) = hash_password(&request.password) {
+                    conn.execute(
+                        "UPDATE users SET password_hash = ?, pw_algo = ?, pw_cost = ? WHERE id = ?",
+                        params![&new_hash, new_algo, new_cost, id],
+                    )
+                    .map_err(|e| format!("Failed to upgrade password hash: {}", e))?;
+                }
+            }
+
             let user = User {
                 id,
                 username,
                 email,
                 created_at,
+                account_status,
             };
 
-            // Set current user
+            // Start a session and set current user
+            let session_token = create_session(&conn, id, request.remember_me)
+                .map_err(|e| format!("Failed to create session: {}", e))?;
             *db_state.current_user.lock().unwrap() = Some(user.clone());
 
             Ok(AuthResponse {
                 success: true,
                 message: "Login successful".to_string(),
                 user: Some(user),
+                session_token: Some(session_token),
             })
         }
         Err(_) => Ok(AuthResponse {
             success: false,
             message: "Invalid username or password".to_string(),
             user: None,
+            session_token: None,
+        }),
+    }
+}
+
+#[tauri::command]
+pub fn resume_session(token: String, db_state: State<'_, DbState>) -> Result<AuthResponse, String> {
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+    prune_expired_sessions(&conn).map_err(|e| format!("Failed to prune sessions: {}", e))?;
+
+    let user_result: Result<(i64, String, Option<String>, String, String), _> = conn.query_row(
+        "SELECT users.id, users.username, users.email, users.created_at, users.account_status
+         FROM sessions
+         JOIN users ON users.id = sessions.user_id
+         WHERE sessions.token = ? AND sessions.expires_at > datetime('now')",
+        params![&token],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    );
+
+    match user_result {
+        Ok((id, username, email, created_at, account_status)) => {
+            conn.execute(
+                "UPDATE sessions SET last_seen = datetime('now') WHERE token = ?",
+                params![&token],
+            )
+            .map_err(|e| format!("Failed to refresh session: {}", e))?;
+
+            let user = User {
+                id,
+                username,
+                email,
+                created_at,
+                account_status,
+            };
+
+            *db_state.current_user.lock().unwrap() = Some(user.clone());
+
+            Ok(AuthResponse {
+                success: true,
+                message: "Session resumed".to_string(),
+                user: Some(user),
+                session_token: Some(token),
+            })
+        }
+        Err(_) => Ok(AuthResponse {
+            success: false,
+            message: "Session expired or not found".to_string(),
+            user: None,
+            session_token: None,
         }),
     }
 }
 
 #[tauri::command]
-pub fn logout_user(db_state: State<'_, DbState>) -> Result<AuthResponse, String> {
+pub fn logout_user(
+    token: Option<String>,
+    db_state: State<'_, DbState>,
+) -> Result<AuthResponse, String> {
+    if let Some(token) = token {
+        let conn = db_state
+            .pool
+            .get()
+            .map_err(|e| format!("Failed to get connection: {}", e))?;
+        conn.execute("DELETE FROM sessions WHERE token = ?", params![&token])
+            .map_err(|e| format!("Failed to delete session: {}", e))?;
+    }
+
     *db_state.current_user.lock().unwrap() = None;
 
     Ok(AuthResponse {
         success: true,
         message: "Logged out successfully".to_string(),
         user: None,
+        session_token: None,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpgradeAccountRequest {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+/// Provisions a new local "skeleton" account so work can be saved before the
+/// user registers, and logs into it immediately. Always creates a fresh
+/// guest; it does not resume an existing one — use `resume_session` for that.
+#[tauri::command]
+pub fn create_guest_account(db_state: State<'_, DbState>) -> Result<AuthResponse, String> {
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    let username = format!("guest-{}", &generate_session_token()[..12]);
+
+    conn.execute(
+        "INSERT INTO users (username, email, password_hash, account_status) VALUES (?, NULL, NULL, 'guest')",
+        params![&username],
+    )
+    .map_err(|e| format!("Failed to create guest account: {}", e))?;
+
+    let user_id = conn.last_insert_rowid();
+
+    conn.execute(
+        "INSERT INTO user_preferences (user_id) VALUES (?)",
+        params![user_id],
+    )
+    .map_err(|e| format!("Failed to create guest preferences: {}", e))?;
+
+    let user = conn
+        .query_row(
+            "SELECT id, username, email, created_at, account_status FROM users WHERE id = ?",
+            params![user_id],
+            |row| {
+                Ok(User {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    email: row.get(2)?,
+                    created_at: row.get(3)?,
+                    account_status: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to fetch guest user: {}", e))?;
+
+    let session_token = create_session(&conn, user_id, false)
+        .map_err(|e| format!("Failed to create session: {}", e))?;
+    *db_state.current_user.lock().unwrap() = Some(user.clone());
+
+    Ok(AuthResponse {
+        success: true,
+        message: "Guest account created".to_string(),
+        user: Some(user),
+        session_token: Some(session_token),
+    })
+}
+
+/// Fills in credentials for a guest account and flips it to `registered`,
+/// preserving all recipes/measurements already tied to its `user_id`.
+#[tauri::command]
+pub fn upgrade_account(
+    request: UpgradeAccountRequest,
+    db_state: State<'_, DbState>,
+) -> Result<AuthResponse, String> {
+    let current_user = require_current_user(&db_state)?;
+
+    if current_user.account_status != "guest" {
+        return Ok(AuthResponse {
+            success: false,
+            message: "Account is already registered".to_string(),
+            user: None,
+            session_token: None,
+        });
+    }
+
+    if request.username.is_empty() || request.email.is_empty() || request.password.is_empty() {
+        return Ok(AuthResponse {
+            success: false,
+            message: "Username, email, and password are required".to_string(),
+            user: None,
+            session_token: None,
+        });
+    }
+
+    if request.password.len() < 6 {
+        return Ok(AuthResponse {
+            success: false,
+            message: "Password must be at least 6 characters".to_string(),
+            user: None,
+            session_token: None,
+        });
+    }
+
+    let (password_hash, pw_algo, pw_cost) = hash_password(&request.password)
+        .map_err(|e| format!("Failed to hash password: {}", e))?;
+
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    let exists: Result<i64, _> = conn.query_row(
+        "SELECT id FROM users WHERE (username = ? OR email = ?) AND id != ?",
+        params![&request.username, &request.email, current_user.id],
+        |row| row.get(0),
+    );
+
+    if exists.is_ok() {
+        return Ok(AuthResponse {
+            success: false,
+            message: "Username or email already exists".to_string(),
+            user: None,
+            session_token: None,
+        });
+    }
+
+    conn.execute(
+        "UPDATE users
+         SET username = ?, email = ?, password_hash = ?, pw_algo = ?, pw_cost = ?, account_status = 'registered'
+         WHERE id = ?",
+        params![
+            &request.username,
+            &request.email,
+            &password_hash,
+            pw_algo,
+            pw_cost,
+            current_user.id
+        ],
+    )
+    .map_err(|e| format!("Failed to upgrade account: {}", e))?;
+
+    // A guest upgrading can be the first real user on the system, so it
+    // needs the same admin bootstrap check as register_user.
+    crate::roles::bootstrap_admin_if_needed(&conn, current_user.id)
+        .map_err(|e| format!("Failed to check/assign admin role: {}", e))?;
+
+    let user = conn
+        .query_row(
+            "SELECT id, username, email, created_at, account_status FROM users WHERE id = ?",
+            params![current_user.id],
+            |row| {
+                Ok(User {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    email: row.get(2)?,
+                    created_at: row.get(3)?,
+                    account_status: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to fetch user: {}", e))?;
+
+    *db_state.current_user.lock().unwrap() = Some(user.clone());
+
+    Ok(AuthResponse {
+        success: true,
+        message: "Account upgraded successfully".to_string(),
+        user: Some(user),
+        session_token: None,
     })
 }
 
@@ -299,3 +602,16 @@ pub fn get_current_user(db_state: State<'_, DbState>) -> Result<Option<User>, St
     let current_user = db_state.current_user.lock().unwrap().clone();
     Ok(current_user)
 }
+
+/// Returns the currently logged-in user, or an error if nobody is logged in.
+///
+/// Shared by command modules (roles, sync) that need to know
+/// who is making the request.
+pub(crate) fn require_current_user(db_state: &State<'_, DbState>) -> Result<User, String> {
+    db_state
+        .current_user
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "Not logged in".to_string())
+}
@@ -2,6 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod auth;
+mod migrations;
+mod roles;
+mod sync;
 
 use tauri::Manager;
 
@@ -32,7 +35,15 @@ fn main() {
             auth::register_user,
             auth::login_user,
             auth::logout_user,
+            auth::resume_session,
+            auth::create_guest_account,
+            auth::upgrade_account,
             auth::get_current_user,
+            roles::grant_role,
+            roles::revoke_role_command,
+            roles::share_recipe,
+            roles::list_recipes,
+            sync::sync,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,240 @@
+use rusqlite::{Connection, Result as SqlResult};
+
+// ============================================================================
+// Schema migrations
+// ============================================================================
+//
+// Each entry is applied at most once, in order. The applied version is
+// tracked in SQLite's `user_version` pragma, so `DbState::new` can be called
+// against an existing database and only the migrations that haven't run yet
+// will execute. Add new tables or columns by appending a migration here
+// rather than editing an earlier one.
+//
+// Most migrations run inside their own transaction (`transactional: true`).
+// A migration that needs to toggle `PRAGMA foreign_keys` — e.g. a SQLite
+// table rebuild to change a column's constraints — must set
+// `transactional: false` instead: SQLite silently ignores `foreign_keys`
+// changes made while a transaction is active, so the pragma has to be
+// flipped outside one.
+
+struct Migration {
+    name: &'static str,
+    sql: &'static str,
+    transactional: bool,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "V1__initial",
+        transactional: true,
+        sql: "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            email TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS user_recipes (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            description TEXT,
+            category TEXT NOT NULL,
+            components TEXT NOT NULL,
+            total_volume REAL NOT NULL,
+            volume_unit TEXT NOT NULL,
+            ph REAL,
+            instructions TEXT,
+            notes TEXT,
+            tags TEXT,
+            created_at TEXT NOT NULL,
+            modified_at TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS user_measurements (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            protein_name TEXT NOT NULL,
+            date TEXT NOT NULL,
+            absorbance_280 REAL NOT NULL,
+            extinction_coefficient REAL NOT NULL,
+            molecular_weight REAL NOT NULL,
+            path_length REAL NOT NULL,
+            concentration REAL NOT NULL,
+            concentration_molar REAL NOT NULL,
+            notes TEXT,
+            sequence TEXT,
+            batch_number TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS user_preferences (
+            user_id INTEGER PRIMARY KEY,
+            default_volume REAL NOT NULL DEFAULT 100,
+            default_volume_unit TEXT NOT NULL DEFAULT 'mL',
+            default_concentration_unit TEXT NOT NULL DEFAULT 'M',
+            recent_chemicals TEXT,
+            favorite_recipes TEXT,
+            theme TEXT NOT NULL DEFAULT 'auto',
+            scientific_notation INTEGER NOT NULL DEFAULT 0,
+            decimal_places INTEGER NOT NULL DEFAULT 4,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        );",
+    },
+    Migration {
+        name: "V2__add_sessions",
+        transactional: true,
+        sql: "CREATE TABLE IF NOT EXISTS sessions (
+            token TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            last_seen TEXT NOT NULL,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+        );",
+    },
+    Migration {
+        name: "V3__add_measurement_instrument",
+        transactional: true,
+        sql: "ALTER TABLE user_measurements ADD COLUMN instrument TEXT;",
+    },
+    Migration {
+        name: "V4__add_password_scheme_columns",
+        transactional: true,
+        // pw_cost backfills to 12, not a placeholder: every pre-existing row
+        // was hashed with bcrypt::DEFAULT_COST, which is 12, so this keeps
+        // the recorded cost truthful and avoids a spurious rehash on every
+        // user's next login.
+        sql: "ALTER TABLE users ADD COLUMN pw_algo TEXT NOT NULL DEFAULT 'bcrypt';
+         ALTER TABLE users ADD COLUMN pw_cost INTEGER NOT NULL DEFAULT 12;",
+    },
+    Migration {
+        name: "V5__add_rbac",
+        transactional: true,
+        sql: "CREATE TABLE IF NOT EXISTS roles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS permissions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS role_permissions (
+            role_id INTEGER NOT NULL,
+            permission_id INTEGER NOT NULL,
+            PRIMARY KEY (role_id, permission_id),
+            FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+            FOREIGN KEY (permission_id) REFERENCES permissions(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS user_roles (
+            user_id INTEGER NOT NULL,
+            role_id INTEGER NOT NULL,
+            PRIMARY KEY (user_id, role_id),
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+        );
+
+        ALTER TABLE user_recipes ADD COLUMN shared_with TEXT;
+
+        INSERT OR IGNORE INTO roles (name) VALUES ('admin');
+        INSERT OR IGNORE INTO permissions (name) VALUES
+            ('view_recipe'), ('edit_recipe'), ('delete_user'), ('manage_roles');
+        INSERT OR IGNORE INTO role_permissions (role_id, permission_id)
+            SELECT r.id, p.id FROM roles r, permissions p WHERE r.name = 'admin';",
+    },
+    Migration {
+        // SQLite can't drop a NOT NULL constraint in place, so the users
+        // table is recreated to let guest accounts have no email/password.
+        // This must run non-transactional: PRAGMA foreign_keys is a no-op
+        // inside a transaction, so running this as one would leave FK
+        // enforcement on through the DROP TABLE and cascade-delete every
+        // row that references users (sessions, user_recipes, ...).
+        name: "V6__add_guest_accounts",
+        transactional: false,
+        sql: "PRAGMA foreign_keys = OFF;
+
+        CREATE TABLE users_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            email TEXT UNIQUE,
+            password_hash TEXT,
+            pw_algo TEXT NOT NULL DEFAULT 'bcrypt',
+            pw_cost INTEGER NOT NULL DEFAULT 12,
+            account_status TEXT NOT NULL DEFAULT 'registered',
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        INSERT INTO users_new (id, username, email, password_hash, pw_algo, pw_cost, account_status, created_at)
+            SELECT id, username, email, password_hash, pw_algo, pw_cost, 'registered', created_at FROM users;
+
+        DROP TABLE users;
+        ALTER TABLE users_new RENAME TO users;
+
+        PRAGMA foreign_keys = ON;",
+    },
+    Migration {
+        name: "V7__add_sync_columns",
+        transactional: true,
+        sql: "ALTER TABLE user_recipes ADD COLUMN sync_id TEXT;
+         ALTER TABLE user_recipes ADD COLUMN sync_version INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE user_recipes ADD COLUMN deleted INTEGER NOT NULL DEFAULT 0;
+
+         ALTER TABLE user_measurements ADD COLUMN sync_id TEXT;
+         ALTER TABLE user_measurements ADD COLUMN sync_version INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE user_measurements ADD COLUMN deleted INTEGER NOT NULL DEFAULT 0;
+
+         CREATE TABLE IF NOT EXISTS sync_counter (
+             id INTEGER PRIMARY KEY CHECK (id = 1),
+             value INTEGER NOT NULL DEFAULT 0
+         );
+         INSERT OR IGNORE INTO sync_counter (id, value) VALUES (1, 0);",
+    },
+    Migration {
+        // Without this, a client that resends an existing item's sync_id
+        // under a different local id inserts a second row sharing that
+        // sync_id: load_*_by_sync_id then picks one of the two arbitrarily,
+        // so conflict detection can read a stale copy. SQLite treats NULL
+        // as distinct from any other value in a unique index, so rows that
+        // haven't been synced yet (sync_id still NULL) don't collide.
+        name: "V8__unique_sync_id",
+        transactional: true,
+        sql: "CREATE UNIQUE INDEX IF NOT EXISTS idx_user_recipes_user_sync_id
+                ON user_recipes(user_id, sync_id);
+         CREATE UNIQUE INDEX IF NOT EXISTS idx_user_measurements_user_sync_id
+                ON user_measurements(user_id, sync_id);",
+    },
+];
+
+/// Brings `conn` up to the latest schema version, running every migration
+/// newer than the database's current `user_version`. Migrations run inside
+/// their own transaction unless marked `transactional: false`, which a
+/// migration needs when it must toggle `PRAGMA foreign_keys` itself.
+pub fn run_migrations(conn: &Connection) -> SqlResult<()> {
+    let current_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate() {
+        let version = (i + 1) as i64;
+        if version <= current_version {
+            continue;
+        }
+
+        eprintln!("Applying migration {}", migration.name);
+
+        if migration.transactional {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute_batch(migration.sql)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()?;
+        } else {
+            conn.execute_batch(migration.sql)?;
+            conn.pragma_update(None, "user_version", version)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,371 @@
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::auth::{require_current_user, DbState};
+
+// ============================================================================
+// Types
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleRequest {
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareRecipeRequest {
+    pub recipe_id: String,
+    /// `None` makes the recipe private again; `Some("all")` shares it with
+    /// every user; `Some(role)` shares it with members of that role.
+    pub shared_with: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeSummary {
+    pub id: String,
+    pub user_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub category: String,
+    pub components: String,
+    pub total_volume: f64,
+    pub volume_unit: String,
+    pub ph: Option<f64>,
+    pub instructions: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Option<String>,
+    pub shared_with: Option<String>,
+    pub created_at: String,
+    pub modified_at: String,
+}
+
+// ============================================================================
+// Permission checks
+// ============================================================================
+
+/// Returns whether `user_id` holds a role granting `permission`.
+pub fn user_has_permission(conn: &Connection, user_id: i64, permission: &str) -> SqlResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*)
+         FROM user_roles
+         JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+         JOIN permissions ON permissions.id = role_permissions.permission_id
+         WHERE user_roles.user_id = ? AND permissions.name = ?",
+        params![user_id, permission],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Returns whether any user currently holds the `admin` role.
+///
+/// Used to decide whether a newly registered or upgraded account should be
+/// bootstrapped as `admin` — counting total `users` rows doesn't work once
+/// guest accounts can exist before the first real registration.
+pub fn admin_exists(conn: &Connection) -> SqlResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*)
+         FROM user_roles
+         JOIN roles ON roles.id = user_roles.role_id
+         WHERE roles.name = 'admin'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Grants `user_id` the `admin` role if no one currently holds it.
+///
+/// Called from both `register_user` and `upgrade_account`, since either can
+/// produce the first real user on a fresh install.
+pub fn bootstrap_admin_if_needed(conn: &Connection, user_id: i64) -> SqlResult<()> {
+    if !admin_exists(conn)? {
+        assign_role(conn, user_id, "admin")?;
+    }
+    Ok(())
+}
+
+/// Assigns `role_name` to `user_id`, creating the role if it doesn't exist.
+///
+/// Used to seed the first registered user as `admin`; new roles otherwise
+/// come with no permissions until granted via `role_permissions`.
+pub fn assign_role(conn: &Connection, user_id: i64, role_name: &str) -> SqlResult<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO roles (name) VALUES (?)",
+        params![role_name],
+    )?;
+
+    let role_id: i64 = conn.query_row(
+        "SELECT id FROM roles WHERE name = ?",
+        params![role_name],
+        |row| row.get(0),
+    )?;
+
+    conn.execute(
+        "INSERT OR IGNORE INTO user_roles (user_id, role_id) VALUES (?, ?)",
+        params![user_id, role_id],
+    )?;
+
+    Ok(())
+}
+
+fn revoke_role(conn: &Connection, user_id: i64, role_name: &str) -> SqlResult<()> {
+    conn.execute(
+        "DELETE FROM user_roles
+         WHERE user_id = ? AND role_id = (SELECT id FROM roles WHERE name = ?)",
+        params![user_id, role_name],
+    )?;
+    Ok(())
+}
+
+/// Recipes visible to `user_id`: ones they own, ones shared with everyone
+/// (`shared_with = 'all'`), ones shared with a role they hold, or any
+/// recipe at all if they hold the blanket `view_recipe` permission.
+fn visible_recipes(conn: &Connection, user_id: i64) -> SqlResult<Vec<RecipeSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_id, name, description, category, components, total_volume,
+                volume_unit, ph, instructions, notes, tags, shared_with, created_at, modified_at
+         FROM user_recipes
+         WHERE deleted = 0
+           AND (
+                user_id = ?1
+                OR shared_with = 'all'
+                OR shared_with IN (
+                    SELECT roles.name FROM user_roles
+                    JOIN roles ON roles.id = user_roles.role_id
+                    WHERE user_roles.user_id = ?1
+                )
+                OR EXISTS (
+                    SELECT 1 FROM user_roles
+                    JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+                    JOIN permissions ON permissions.id = role_permissions.permission_id
+                    WHERE user_roles.user_id = ?1 AND permissions.name = 'view_recipe'
+                )
+           )",
+    )?;
+
+    let rows = stmt.query_map(params![user_id], |row| {
+        Ok(RecipeSummary {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            name: row.get(2)?,
+            description: row.get(3)?,
+            category: row.get(4)?,
+            components: row.get(5)?,
+            total_volume: row.get(6)?,
+            volume_unit: row.get(7)?,
+            ph: row.get(8)?,
+            instructions: row.get(9)?,
+            notes: row.get(10)?,
+            tags: row.get(11)?,
+            shared_with: row.get(12)?,
+            created_at: row.get(13)?,
+            modified_at: row.get(14)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn find_user_id(conn: &Connection, username: &str) -> SqlResult<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM users WHERE username = ?",
+        params![username],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub fn grant_role(
+    request: RoleRequest,
+    db_state: State<'_, DbState>,
+) -> Result<RoleResponse, String> {
+    let current_user = require_current_user(&db_state)?;
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    if !user_has_permission(&conn, current_user.id, "manage_roles")
+        .map_err(|e| format!("Failed to check permissions: {}", e))?
+    {
+        return Ok(RoleResponse {
+            success: false,
+            message: "You don't have permission to manage roles".to_string(),
+        });
+    }
+
+    let target_user_id = find_user_id(&conn, &request.username)
+        .map_err(|e| format!("Failed to look up user: {}", e))?;
+
+    let Some(target_user_id) = target_user_id else {
+        return Ok(RoleResponse {
+            success: false,
+            message: "No such user".to_string(),
+        });
+    };
+
+    assign_role(&conn, target_user_id, &request.role)
+        .map_err(|e| format!("Failed to grant role: {}", e))?;
+
+    Ok(RoleResponse {
+        success: true,
+        message: format!("Granted '{}' to {}", request.role, request.username),
+    })
+}
+
+#[tauri::command]
+pub fn revoke_role_command(
+    request: RoleRequest,
+    db_state: State<'_, DbState>,
+) -> Result<RoleResponse, String> {
+    let current_user = require_current_user(&db_state)?;
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    if !user_has_permission(&conn, current_user.id, "manage_roles")
+        .map_err(|e| format!("Failed to check permissions: {}", e))?
+    {
+        return Ok(RoleResponse {
+            success: false,
+            message: "You don't have permission to manage roles".to_string(),
+        });
+    }
+
+    let target_user_id = find_user_id(&conn, &request.username)
+        .map_err(|e| format!("Failed to look up user: {}", e))?;
+
+    let Some(target_user_id) = target_user_id else {
+        return Ok(RoleResponse {
+            success: false,
+            message: "No such user".to_string(),
+        });
+    };
+
+    revoke_role(&conn, target_user_id, &request.role)
+        .map_err(|e| format!("Failed to revoke role: {}", e))?;
+
+    Ok(RoleResponse {
+        success: true,
+        message: format!("Revoked '{}' from {}", request.role, request.username),
+    })
+}
+
+#[tauri::command]
+pub fn list_recipes(db_state: State<'_, DbState>) -> Result<Vec<RecipeSummary>, String> {
+    let current_user = require_current_user(&db_state)?;
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    visible_recipes(&conn, current_user.id).map_err(|e| format!("Failed to list recipes: {}", e))
+}
+
+#[tauri::command]
+pub fn share_recipe(
+    request: ShareRecipeRequest,
+    db_state: State<'_, DbState>,
+) -> Result<RoleResponse, String> {
+    let current_user = require_current_user(&db_state)?;
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    let owner_id: Option<i64> = conn
+        .query_row(
+            "SELECT user_id FROM user_recipes WHERE id = ?",
+            params![&request.recipe_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to look up recipe: {}", e))?;
+
+    let Some(owner_id) = owner_id else {
+        return Ok(RoleResponse {
+            success: false,
+            message: "No such recipe".to_string(),
+        });
+    };
+
+    let can_share = owner_id == current_user.id
+        || user_has_permission(&conn, current_user.id, "edit_recipe")
+            .map_err(|e| format!("Failed to check permissions: {}", e))?;
+
+    if !can_share {
+        return Ok(RoleResponse {
+            success: false,
+            message: "You don't have permission to share this recipe".to_string(),
+        });
+    }
+
+    conn.execute(
+        "UPDATE user_recipes SET shared_with = ? WHERE id = ?",
+        params![&request.shared_with, &request.recipe_id],
+    )
+    .map_err(|e| format!("Failed to update recipe sharing: {}", e))?;
+
+    Ok(RoleResponse {
+        success: true,
+        message: "Recipe sharing updated".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn insert_user(conn: &Connection, username: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO users (username, email, password_hash) VALUES (?, ?, ?)",
+            params![username, format!("{username}@example.com"), "hash"],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[test]
+    fn bootstrap_admin_if_needed_skips_a_guest_created_before_registration() {
+        let conn = test_conn();
+
+        // A guest account (e.g. from create_guest_account) exists before
+        // anyone registers, so the users table already has a row.
+        conn.execute(
+            "INSERT INTO users (username, email, password_hash, account_status)
+             VALUES ('guest-abc123', NULL, NULL, 'guest')",
+            [],
+        )
+        .unwrap();
+
+        assert!(!admin_exists(&conn).unwrap());
+
+        let first_user = insert_user(&conn, "alice");
+        bootstrap_admin_if_needed(&conn, first_user).unwrap();
+        assert!(user_has_permission(&conn, first_user, "manage_roles").unwrap());
+
+        let second_user = insert_user(&conn, "bob");
+        bootstrap_admin_if_needed(&conn, second_user).unwrap();
+        assert!(!user_has_permission(&conn, second_user, "manage_roles").unwrap());
+    }
+}
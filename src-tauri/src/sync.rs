@@ -0,0 +1,502 @@
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tauri::State;
+
+use crate::auth::{require_current_user, DbState};
+
+// ============================================================================
+// Types
+// ============================================================================
+//
+// Each item mirrors its table's columns plus three sync bookkeeping fields:
+// `sync_id` (stable across devices), `sync_version` (bumped on every write,
+// used for conflict detection) and `deleted` (a soft-delete flag). The
+// `components`/`instructions` JSON text columns are passed through as-is —
+// this module never parses them.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeItem {
+    pub id: String,
+    pub sync_id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub category: String,
+    pub components: String,
+    pub total_volume: f64,
+    pub volume_unit: String,
+    pub ph: Option<f64>,
+    pub instructions: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Option<String>,
+    pub sync_version: i64,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeasurementItem {
+    pub id: String,
+    pub sync_id: Option<String>,
+    pub protein_name: String,
+    pub date: String,
+    pub absorbance_280: f64,
+    pub extinction_coefficient: f64,
+    pub molecular_weight: f64,
+    pub path_length: f64,
+    pub concentration: f64,
+    pub concentration_molar: f64,
+    pub notes: Option<String>,
+    pub sequence: Option<String>,
+    pub batch_number: Option<String>,
+    pub instrument: Option<String>,
+    pub sync_version: i64,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SyncItem {
+    Recipe(RecipeItem),
+    Measurement(MeasurementItem),
+}
+
+impl SyncItem {
+    fn sync_id(&self) -> Option<&str> {
+        match self {
+            SyncItem::Recipe(item) => item.sync_id.as_deref(),
+            SyncItem::Measurement(item) => item.sync_id.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncRequest {
+    pub items: Vec<SyncItem>,
+    pub last_sync_token: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncResponse {
+    /// Items changed on the server since `last_sync_token` (including the
+    /// ones the caller just pushed), except those returned in `conflicts`.
+    pub updated: Vec<SyncItem>,
+    /// Items the caller pushed with a stale `sync_version` — the server's
+    /// copy is returned here instead of being overwritten, for the UI to
+    /// merge.
+    pub conflicts: Vec<SyncItem>,
+    pub sync_token: i64,
+}
+
+/// Generates a random, hex-encoded `sync_id` for a newly created item.
+fn generate_sync_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bumps and returns the server's monotonic sync counter.
+fn next_sync_version(conn: &Connection) -> SqlResult<i64> {
+    conn.execute("UPDATE sync_counter SET value = value + 1 WHERE id = 1", [])?;
+    conn.query_row("SELECT value FROM sync_counter WHERE id = 1", [], |row| {
+        row.get(0)
+    })
+}
+
+fn current_sync_version(conn: &Connection) -> SqlResult<i64> {
+    conn.query_row("SELECT value FROM sync_counter WHERE id = 1", [], |row| {
+        row.get(0)
+    })
+}
+
+// ============================================================================
+// Recipes
+// ============================================================================
+
+fn load_recipe_by_sync_id(
+    conn: &Connection,
+    user_id: i64,
+    sync_id: &str,
+) -> SqlResult<Option<RecipeItem>> {
+    conn.query_row(
+        "SELECT id, sync_id, name, description, category, components, total_volume,
+                volume_unit, ph, instructions, notes, tags, sync_version, deleted
+         FROM user_recipes WHERE user_id = ? AND sync_id = ?",
+        params![user_id, sync_id],
+        |row| {
+            Ok(RecipeItem {
+                id: row.get(0)?,
+                sync_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                category: row.get(4)?,
+                components: row.get(5)?,
+                total_volume: row.get(6)?,
+                volume_unit: row.get(7)?,
+                ph: row.get(8)?,
+                instructions: row.get(9)?,
+                notes: row.get(10)?,
+                tags: row.get(11)?,
+                sync_version: row.get(12)?,
+                deleted: row.get(13)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Applies a locally changed recipe, returning `Some(server copy)` if the
+/// push lost to a newer server version instead of being written.
+fn apply_recipe_change(
+    conn: &Connection,
+    user_id: i64,
+    mut item: RecipeItem,
+) -> SqlResult<Option<RecipeItem>> {
+    if let Some(sync_id) = item.sync_id.clone() {
+        if let Some(server_item) = load_recipe_by_sync_id(conn, user_id, &sync_id)? {
+            if server_item.sync_version > item.sync_version {
+                return Ok(Some(server_item));
+            }
+        }
+    } else {
+        item.sync_id = Some(generate_sync_id());
+    }
+
+    let sync_version = next_sync_version(conn)?;
+
+    conn.execute(
+        "INSERT INTO user_recipes
+            (id, user_id, name, description, category, components, total_volume,
+             volume_unit, ph, instructions, notes, tags, created_at, modified_at,
+             sync_id, sync_version, deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'), ?, ?, ?)
+         ON CONFLICT(user_id, sync_id) DO UPDATE SET
+            name = excluded.name,
+            description = excluded.description,
+            category = excluded.category,
+            components = excluded.components,
+            total_volume = excluded.total_volume,
+            volume_unit = excluded.volume_unit,
+            ph = excluded.ph,
+            instructions = excluded.instructions,
+            notes = excluded.notes,
+            tags = excluded.tags,
+            modified_at = datetime('now'),
+            sync_id = excluded.sync_id,
+            sync_version = excluded.sync_version,
+            deleted = excluded.deleted",
+        params![
+            item.id,
+            user_id,
+            item.name,
+            item.description,
+            item.category,
+            item.components,
+            item.total_volume,
+            item.volume_unit,
+            item.ph,
+            item.instructions,
+            item.notes,
+            item.tags,
+            item.sync_id,
+            sync_version,
+            item.deleted,
+        ],
+    )?;
+
+    Ok(None)
+}
+
+fn recipes_changed_since(conn: &Connection, user_id: i64, since: i64) -> SqlResult<Vec<RecipeItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, sync_id, name, description, category, components, total_volume,
+                volume_unit, ph, instructions, notes, tags, sync_version, deleted
+         FROM user_recipes WHERE user_id = ? AND sync_version > ?",
+    )?;
+    let rows = stmt.query_map(params![user_id, since], |row| {
+        Ok(RecipeItem {
+            id: row.get(0)?,
+            sync_id: row.get(1)?,
+            name: row.get(2)?,
+            description: row.get(3)?,
+            category: row.get(4)?,
+            components: row.get(5)?,
+            total_volume: row.get(6)?,
+            volume_unit: row.get(7)?,
+            ph: row.get(8)?,
+            instructions: row.get(9)?,
+            notes: row.get(10)?,
+            tags: row.get(11)?,
+            sync_version: row.get(12)?,
+            deleted: row.get(13)?,
+        })
+    })?;
+    rows.collect()
+}
+
+// ============================================================================
+// Measurements
+// ============================================================================
+
+fn load_measurement_by_sync_id(
+    conn: &Connection,
+    user_id: i64,
+    sync_id: &str,
+) -> SqlResult<Option<MeasurementItem>> {
+    conn.query_row(
+        "SELECT id, sync_id, protein_name, date, absorbance_280, extinction_coefficient,
+                molecular_weight, path_length, concentration, concentration_molar, notes,
+                sequence, batch_number, instrument, sync_version, deleted
+         FROM user_measurements WHERE user_id = ? AND sync_id = ?",
+        params![user_id, sync_id],
+        row_to_measurement_item,
+    )
+    .optional()
+}
+
+fn row_to_measurement_item(row: &rusqlite::Row<'_>) -> SqlResult<MeasurementItem> {
+    Ok(MeasurementItem {
+        id: row.get(0)?,
+        sync_id: row.get(1)?,
+        protein_name: row.get(2)?,
+        date: row.get(3)?,
+        absorbance_280: row.get(4)?,
+        extinction_coefficient: row.get(5)?,
+        molecular_weight: row.get(6)?,
+        path_length: row.get(7)?,
+        concentration: row.get(8)?,
+        concentration_molar: row.get(9)?,
+        notes: row.get(10)?,
+        sequence: row.get(11)?,
+        batch_number: row.get(12)?,
+        instrument: row.get(13)?,
+        sync_version: row.get(14)?,
+        deleted: row.get(15)?,
+    })
+}
+
+fn apply_measurement_change(
+    conn: &Connection,
+    user_id: i64,
+    mut item: MeasurementItem,
+) -> SqlResult<Option<MeasurementItem>> {
+    if let Some(sync_id) = item.sync_id.clone() {
+        if let Some(server_item) = load_measurement_by_sync_id(conn, user_id, &sync_id)? {
+            if server_item.sync_version > item.sync_version {
+                return Ok(Some(server_item));
+            }
+        }
+    } else {
+        item.sync_id = Some(generate_sync_id());
+    }
+
+    let sync_version = next_sync_version(conn)?;
+
+    conn.execute(
+        "INSERT INTO user_measurements
+            (id, user_id, protein_name, date, absorbance_280, extinction_coefficient,
+             molecular_weight, path_length, concentration, concentration_molar, notes,
+             sequence, batch_number, instrument, sync_id, sync_version, deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(user_id, sync_id) DO UPDATE SET
+            protein_name = excluded.protein_name,
+            date = excluded.date,
+            absorbance_280 = excluded.absorbance_280,
+            extinction_coefficient = excluded.extinction_coefficient,
+            molecular_weight = excluded.molecular_weight,
+            path_length = excluded.path_length,
+            concentration = excluded.concentration,
+            concentration_molar = excluded.concentration_molar,
+            notes = excluded.notes,
+            sequence = excluded.sequence,
+            batch_number = excluded.batch_number,
+            instrument = excluded.instrument,
+            sync_id = excluded.sync_id,
+            sync_version = excluded.sync_version,
+            deleted = excluded.deleted",
+        params![
+            item.id,
+            user_id,
+            item.protein_name,
+            item.date,
+            item.absorbance_280,
+            item.extinction_coefficient,
+            item.molecular_weight,
+            item.path_length,
+            item.concentration,
+            item.concentration_molar,
+            item.notes,
+            item.sequence,
+            item.batch_number,
+            item.instrument,
+            item.sync_id,
+            sync_version,
+            item.deleted,
+        ],
+    )?;
+
+    Ok(None)
+}
+
+fn measurements_changed_since(
+    conn: &Connection,
+    user_id: i64,
+    since: i64,
+) -> SqlResult<Vec<MeasurementItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, sync_id, protein_name, date, absorbance_280, extinction_coefficient,
+                molecular_weight, path_length, concentration, concentration_molar, notes,
+                sequence, batch_number, instrument, sync_version, deleted
+         FROM user_measurements WHERE user_id = ? AND sync_version > ?",
+    )?;
+    let rows = stmt.query_map(params![user_id, since], row_to_measurement_item)?;
+    rows.collect()
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Pushes locally changed recipes/measurements and pulls everything the
+/// server has seen since `last_sync_token`, returning a new token to use on
+/// the next call. Items that lost a conflict are returned separately with
+/// the server's copy rather than being overwritten.
+#[tauri::command]
+pub fn sync(request: SyncRequest, db_state: State<'_, DbState>) -> Result<SyncResponse, String> {
+    let current_user = require_current_user(&db_state)?;
+    let conn = db_state
+        .pool
+        .get()
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+
+    perform_sync(&conn, current_user.id, request).map_err(|e| format!("Sync failed: {}", e))
+}
+
+fn perform_sync(conn: &Connection, user_id: i64, request: SyncRequest) -> SqlResult<SyncResponse> {
+    let mut conflicts = Vec::new();
+
+    for item in request.items {
+        match item {
+            SyncItem::Recipe(recipe) => {
+                if let Some(server_copy) = apply_recipe_change(conn, user_id, recipe)? {
+                    conflicts.push(SyncItem::Recipe(server_copy));
+                }
+            }
+            SyncItem::Measurement(measurement) => {
+                if let Some(server_copy) = apply_measurement_change(conn, user_id, measurement)? {
+                    conflicts.push(SyncItem::Measurement(server_copy));
+                }
+            }
+        }
+    }
+
+    // Conflicted items are already returned (with the server's copy) in
+    // `conflicts`; without this, any whose server copy is newer than the
+    // caller's last_sync_token would also show up in `updated` below.
+    let conflicted_sync_ids: HashSet<&str> =
+        conflicts.iter().filter_map(SyncItem::sync_id).collect();
+
+    let since = request.last_sync_token.unwrap_or(0);
+    let mut updated = Vec::new();
+    updated.extend(
+        recipes_changed_since(conn, user_id, since)?
+            .into_iter()
+            .map(SyncItem::Recipe),
+    );
+    updated.extend(
+        measurements_changed_since(conn, user_id, since)?
+            .into_iter()
+            .map(SyncItem::Measurement),
+    );
+    updated.retain(|item| {
+        item.sync_id()
+            .map_or(true, |sync_id| !conflicted_sync_ids.contains(sync_id))
+    });
+
+    let sync_token = current_sync_version(conn)?;
+
+    Ok(SyncResponse {
+        updated,
+        conflicts,
+        sync_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn new_recipe(id: &str, name: &str, sync_id: Option<String>, sync_version: i64) -> RecipeItem {
+        RecipeItem {
+            id: id.to_string(),
+            sync_id,
+            name: name.to_string(),
+            description: None,
+            category: "buffer".to_string(),
+            components: "[]".to_string(),
+            total_volume: 100.0,
+            volume_unit: "mL".to_string(),
+            ph: None,
+            instructions: None,
+            notes: None,
+            tags: None,
+            sync_version,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn conflicted_item_is_not_also_returned_in_updated() {
+        let conn = test_conn();
+        // Migrations leave foreign keys enforced, so the owner must exist.
+        conn.execute("INSERT INTO users (username) VALUES ('alice')", [])
+            .unwrap();
+        let user_id = conn.last_insert_rowid();
+
+        // Device A creates the recipe.
+        let response = perform_sync(
+            &conn,
+            user_id,
+            SyncRequest {
+                items: vec![SyncItem::Recipe(new_recipe("r1", "Original", None, 0))],
+                last_sync_token: Some(0),
+            },
+        )
+        .unwrap();
+        assert!(response.conflicts.is_empty());
+        let sync_id = match &response.updated[0] {
+            SyncItem::Recipe(r) => r.sync_id.clone().unwrap(),
+            _ => panic!("expected a recipe"),
+        };
+
+        // Device B pushes a stale edit (sync_version 0, but the server is
+        // already past that) of the same item.
+        let response = perform_sync(
+            &conn,
+            user_id,
+            SyncRequest {
+                items: vec![SyncItem::Recipe(new_recipe(
+                    "r1",
+                    "Stale edit",
+                    Some(sync_id.clone()),
+                    0,
+                ))],
+                last_sync_token: Some(0),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(response.conflicts.len(), 1);
+        assert!(
+            response.updated.is_empty(),
+            "conflicted item should not also appear in updated: {:?}",
+            response.updated
+        );
+    }
+}